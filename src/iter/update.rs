@@ -2,6 +2,30 @@ use super::plumbing::*;
 use super::*;
 
 use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Splitting preferences that [`WithSetup`] attaches to a producer,
+/// independent of any one adaptor's own `min_len`/`max_len`.
+///
+/// `min_len`/`max_len` are applied directly to the wrapped producer (see
+/// [`WithSetup::with_producer`]), the same knobs `with_min_len`/
+/// `with_max_len` already expose per-stage. `splits` is carried through
+/// for forward compatibility but, lacking a `Producer`-level hook for a
+/// desired split count, is not yet consulted by anything.
+///
+/// [`WithSetup`]: struct.WithSetup.html
+/// [`WithSetup::with_producer`]: struct.WithSetup.html#method.with_producer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Setup {
+    /// The smallest slice of work that should be handed to a single thread.
+    pub min_len: Option<usize>,
+    /// The largest slice of work that should be handed to a single thread.
+    pub max_len: Option<usize>,
+    /// The number of splits/threads the caller would like rayon to use.
+    pub splits: Option<usize>,
+}
 
 /// `Update` is an iterator that mutates the elements of an
 /// underlying iterator before they are yielded.
@@ -327,3 +351,1212 @@ where
         }
     }
 }
+
+/// ////////////////////////////////////////////////////////////////////////
+
+/// `UpdateWithIndex` is an iterator that mutates the elements of an
+/// underlying indexed iterator before they are yielded, giving the
+/// mutating closure the absolute index of each element within the
+/// original iterator.
+///
+/// This struct is created by the [`update_with_index()`] method on
+/// [`IndexedParallelIterator`].
+///
+/// [`update_with_index()`]: trait.IndexedParallelIterator.html#method.update_with_index
+/// [`IndexedParallelIterator`]: trait.IndexedParallelIterator.html
+#[must_use = "iterator adaptors are lazy and do nothing unless consumed"]
+#[derive(Clone)]
+pub struct UpdateWithIndex<I: IndexedParallelIterator, F> {
+    base: I,
+    update_op: F,
+}
+
+impl<I: IndexedParallelIterator + Debug, F> Debug for UpdateWithIndex<I, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UpdateWithIndex")
+            .field("base", &self.base)
+            .finish()
+    }
+}
+
+/// Create a new `UpdateWithIndex` iterator.
+///
+/// NB: a free fn because it is NOT part of the end-user API.
+pub fn new_with_index<I, F>(base: I, update_op: F) -> UpdateWithIndex<I, F>
+where
+    I: IndexedParallelIterator,
+{
+    UpdateWithIndex {
+        base: base,
+        update_op: update_op,
+    }
+}
+
+impl<I, F> ParallelIterator for UpdateWithIndex<I, F>
+where
+    I: IndexedParallelIterator,
+    F: Fn(usize, &mut I::Item) + Send + Sync,
+{
+    type Item = I::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<I, F> IndexedParallelIterator for UpdateWithIndex<I, F>
+where
+    I: IndexedParallelIterator,
+    F: Fn(usize, &mut I::Item) + Send + Sync,
+{
+    fn drive<C>(self, consumer: C) -> C::Result
+    where
+        C: Consumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    fn len(&self) -> usize {
+        self.base.len()
+    }
+
+    fn with_producer<CB>(self, callback: CB) -> CB::Output
+    where
+        CB: ProducerCallback<Self::Item>,
+    {
+        return self.base.with_producer(Callback {
+            callback: callback,
+            update_op: self.update_op,
+        });
+
+        struct Callback<CB, F> {
+            callback: CB,
+            update_op: F,
+        }
+
+        impl<T, F, CB> ProducerCallback<T> for Callback<CB, F>
+        where
+            CB: ProducerCallback<T>,
+            F: Fn(usize, &mut T) + Send + Sync,
+        {
+            type Output = CB::Output;
+
+            fn callback<P>(self, base: P) -> CB::Output
+            where
+                P: Producer<Item = T>,
+            {
+                let producer = UpdateIndexedProducer {
+                    base: base,
+                    update_op: &self.update_op,
+                    offset: 0,
+                };
+                self.callback.callback(producer)
+            }
+        }
+    }
+}
+
+/// ////////////////////////////////////////////////////////////////////////
+
+struct UpdateIndexedProducer<'f, P, F: 'f> {
+    base: P,
+    update_op: &'f F,
+    offset: usize,
+}
+
+impl<'f, P, F> Producer for UpdateIndexedProducer<'f, P, F>
+where
+    P: Producer,
+    F: Fn(usize, &mut P::Item) + Send + Sync,
+{
+    type Item = P::Item;
+    type IntoIter = UpdateIndexedSeq<P::IntoIter, &'f F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        UpdateIndexedSeq {
+            base: self.base.into_iter(),
+            update_op: self.update_op,
+            index: self.offset,
+        }
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let (left, right) = self.base.split_at(index);
+        (
+            UpdateIndexedProducer {
+                base: left,
+                update_op: self.update_op,
+                offset: self.offset,
+            },
+            UpdateIndexedProducer {
+                base: right,
+                update_op: self.update_op,
+                offset: self.offset + index,
+            },
+        )
+    }
+
+    fn fold_with<G>(self, folder: G) -> G
+    where
+        G: Folder<Self::Item>,
+    {
+        let folder1 = UpdateIndexedFolder {
+            base: folder,
+            update_op: self.update_op,
+            index: self.offset,
+        };
+        self.base.fold_with(folder1).base
+    }
+}
+
+struct UpdateIndexedFolder<'f, C, F: 'f> {
+    base: C,
+    update_op: &'f F,
+    index: usize,
+}
+
+impl<'f, T, C, F> Folder<T> for UpdateIndexedFolder<'f, C, F>
+where
+    C: Folder<T>,
+    F: Fn(usize, &mut T),
+{
+    type Result = C::Result;
+
+    fn consume(self, mut item: T) -> Self {
+        (self.update_op)(self.index, &mut item);
+
+        UpdateIndexedFolder {
+            base: self.base.consume(item),
+            update_op: self.update_op,
+            index: self.index + 1,
+        }
+    }
+
+    fn complete(self) -> C::Result {
+        self.base.complete()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+/// Indexed counterpart of `UpdateSeq`: like it, but threads an absolute
+/// running index (starting at the producer's `offset`) through to the
+/// mutating closure.
+#[derive(Debug, Clone)]
+struct UpdateIndexedSeq<I, F> {
+    base: I,
+    update_op: F,
+    index: usize,
+}
+
+impl<I, F> Iterator for UpdateIndexedSeq<I, F>
+where
+    I: Iterator,
+    F: FnMut(usize, &mut I::Item),
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.index;
+        if let Some(mut v) = self.base.next() {
+            (self.update_op)(index, &mut v);
+            self.index += 1;
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+
+    fn fold<Acc, G>(self, init: Acc, mut g: G) -> Acc
+    where
+        G: FnMut(Acc, Self::Item) -> Acc,
+    {
+        let mut f = self.update_op;
+        let mut index = self.index;
+        self.base.fold(init, move |acc, mut v| {
+            f(index, &mut v);
+            index += 1;
+            g(acc, v)
+        })
+    }
+}
+
+impl<I, F> ExactSizeIterator for UpdateIndexedSeq<I, F>
+where
+    I: ExactSizeIterator,
+    F: FnMut(usize, &mut I::Item),
+{}
+
+impl<I, F> DoubleEndedIterator for UpdateIndexedSeq<I, F>
+where
+    I: ExactSizeIterator + DoubleEndedIterator,
+    F: FnMut(usize, &mut I::Item),
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if let Some(mut v) = self.base.next_back() {
+            let index = self.index + self.base.len();
+            (self.update_op)(index, &mut v);
+            Some(v)
+        } else {
+            None
+        }
+    }
+}
+
+/// ////////////////////////////////////////////////////////////////////////
+
+/// `UpdateWith` is an iterator that mutates the elements of an underlying
+/// iterator using some cloneable per-job state, before they are yielded.
+///
+/// This struct is created by the [`update_with()`] method on [`ParallelIterator`]
+///
+/// [`update_with()`]: trait.ParallelIterator.html#method.update_with
+/// [`ParallelIterator`]: trait.ParallelIterator.html
+#[must_use = "iterator adaptors are lazy and do nothing unless consumed"]
+#[derive(Clone)]
+pub struct UpdateWith<I: ParallelIterator, S, F> {
+    base: I,
+    init: S,
+    update_op: F,
+}
+
+impl<I: ParallelIterator + Debug, S: Debug, F> Debug for UpdateWith<I, S, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UpdateWith")
+            .field("base", &self.base)
+            .field("init", &self.init)
+            .finish()
+    }
+}
+
+/// Create a new `UpdateWith` iterator.
+///
+/// NB: a free fn because it is NOT part of the end-user API.
+pub fn new_with<I, S, F>(base: I, init: S, update_op: F) -> UpdateWith<I, S, F>
+where
+    I: ParallelIterator,
+{
+    UpdateWith {
+        base: base,
+        init: init,
+        update_op: update_op,
+    }
+}
+
+impl<I, S, F> ParallelIterator for UpdateWith<I, S, F>
+where
+    I: ParallelIterator,
+    S: Clone + Send,
+    F: Fn(&mut S, &mut I::Item) + Send + Sync,
+{
+    type Item = I::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let consumer1 = UpdateWithConsumer::new(consumer, &self.update_op, self.init);
+        self.base.drive_unindexed(consumer1)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.base.opt_len()
+    }
+}
+
+impl<I, S, F> IndexedParallelIterator for UpdateWith<I, S, F>
+where
+    I: IndexedParallelIterator,
+    S: Clone + Send,
+    F: Fn(&mut S, &mut I::Item) + Send + Sync,
+{
+    fn drive<C>(self, consumer: C) -> C::Result
+    where
+        C: Consumer<Self::Item>,
+    {
+        let consumer1 = UpdateWithConsumer::new(consumer, &self.update_op, self.init);
+        self.base.drive(consumer1)
+    }
+
+    fn len(&self) -> usize {
+        self.base.len()
+    }
+
+    fn with_producer<CB>(self, callback: CB) -> CB::Output
+    where
+        CB: ProducerCallback<Self::Item>,
+    {
+        return self.base.with_producer(Callback {
+            callback: callback,
+            init: self.init,
+            update_op: self.update_op,
+        });
+
+        struct Callback<CB, S, F> {
+            callback: CB,
+            init: S,
+            update_op: F,
+        }
+
+        impl<T, S, F, CB> ProducerCallback<T> for Callback<CB, S, F>
+        where
+            CB: ProducerCallback<T>,
+            S: Clone + Send,
+            F: Fn(&mut S, &mut T) + Send + Sync,
+        {
+            type Output = CB::Output;
+
+            fn callback<P>(self, base: P) -> CB::Output
+            where
+                P: Producer<Item = T>,
+            {
+                let producer = UpdateWithProducer {
+                    base: base,
+                    init: self.init,
+                    update_op: &self.update_op,
+                };
+                self.callback.callback(producer)
+            }
+        }
+    }
+}
+
+/// ////////////////////////////////////////////////////////////////////////
+
+struct UpdateWithProducer<'f, P, S, F: 'f> {
+    base: P,
+    init: S,
+    update_op: &'f F,
+}
+
+impl<'f, P, S, F> Producer for UpdateWithProducer<'f, P, S, F>
+where
+    P: Producer,
+    S: Clone + Send,
+    F: Fn(&mut S, &mut P::Item) + Send + Sync,
+{
+    type Item = P::Item;
+    type IntoIter = UpdateWithSeq<P::IntoIter, S, &'f F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        UpdateWithSeq {
+            base: self.base.into_iter(),
+            state: self.init,
+            update_op: self.update_op,
+        }
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let (left, right) = self.base.split_at(index);
+        (
+            UpdateWithProducer {
+                base: left,
+                init: self.init.clone(),
+                update_op: self.update_op,
+            },
+            UpdateWithProducer {
+                base: right,
+                init: self.init,
+                update_op: self.update_op,
+            },
+        )
+    }
+
+    fn fold_with<G>(self, folder: G) -> G
+    where
+        G: Folder<Self::Item>,
+    {
+        let folder1 = UpdateWithFolder {
+            base: folder,
+            state: self.init,
+            update_op: self.update_op,
+        };
+        self.base.fold_with(folder1).base
+    }
+}
+
+/// ////////////////////////////////////////////////////////////////////////
+/// Consumer implementation
+
+struct UpdateWithConsumer<'f, C, S, F: 'f> {
+    base: C,
+    init: S,
+    update_op: &'f F,
+}
+
+impl<'f, C, S, F> UpdateWithConsumer<'f, C, S, F> {
+    fn new(base: C, update_op: &'f F, init: S) -> Self {
+        UpdateWithConsumer {
+            base: base,
+            init: init,
+            update_op: update_op,
+        }
+    }
+}
+
+impl<'f, T, C, S, F> Consumer<T> for UpdateWithConsumer<'f, C, S, F>
+where
+    C: Consumer<T>,
+    S: Clone + Send,
+    F: Fn(&mut S, &mut T) + Send + Sync,
+{
+    type Folder = UpdateWithFolder<'f, C::Folder, S, F>;
+    type Reducer = C::Reducer;
+    type Result = C::Result;
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+        (
+            UpdateWithConsumer::new(left, self.update_op, self.init.clone()),
+            UpdateWithConsumer::new(right, self.update_op, self.init),
+            reducer,
+        )
+    }
+
+    fn into_folder(self) -> Self::Folder {
+        UpdateWithFolder {
+            base: self.base.into_folder(),
+            state: self.init,
+            update_op: self.update_op,
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+impl<'f, T, C, S, F> UnindexedConsumer<T> for UpdateWithConsumer<'f, C, S, F>
+where
+    C: UnindexedConsumer<T>,
+    S: Clone + Send,
+    F: Fn(&mut S, &mut T) + Send + Sync,
+{
+    fn split_off_left(&self) -> Self {
+        UpdateWithConsumer::new(self.base.split_off_left(), self.update_op, self.init.clone())
+    }
+
+    fn to_reducer(&self) -> Self::Reducer {
+        self.base.to_reducer()
+    }
+}
+
+struct UpdateWithFolder<'f, C, S, F: 'f> {
+    base: C,
+    state: S,
+    update_op: &'f F,
+}
+
+impl<'f, T, C, S, F> Folder<T> for UpdateWithFolder<'f, C, S, F>
+where
+    C: Folder<T>,
+    F: Fn(&mut S, &mut T),
+{
+    type Result = C::Result;
+
+    fn consume(mut self, mut item: T) -> Self {
+        (self.update_op)(&mut self.state, &mut item);
+
+        UpdateWithFolder {
+            base: self.base.consume(item),
+            state: self.state,
+            update_op: self.update_op,
+        }
+    }
+
+    fn complete(self) -> C::Result {
+        self.base.complete()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+/// Per-job counterpart of `UpdateSeq`: carries cloned state `S` alongside
+/// the mutating closure so it can be threaded through sequential
+/// iteration as a reusable scratch buffer, RNG, or accumulator.
+#[derive(Debug, Clone)]
+struct UpdateWithSeq<I, S, F> {
+    base: I,
+    state: S,
+    update_op: F,
+}
+
+impl<I, S, F> Iterator for UpdateWithSeq<I, S, F>
+where
+    I: Iterator,
+    F: FnMut(&mut S, &mut I::Item),
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(mut v) = self.base.next() {
+            (self.update_op)(&mut self.state, &mut v);
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+
+    fn fold<Acc, G>(self, init: Acc, mut g: G) -> Acc
+    where
+        G: FnMut(Acc, Self::Item) -> Acc,
+    {
+        let mut f = self.update_op;
+        let mut state = self.state;
+        self.base.fold(init, move |acc, mut v| {
+            f(&mut state, &mut v);
+            g(acc, v)
+        })
+    }
+}
+
+impl<I, S, F> ExactSizeIterator for UpdateWithSeq<I, S, F>
+where
+    I: ExactSizeIterator,
+    F: FnMut(&mut S, &mut I::Item),
+{}
+
+impl<I, S, F> DoubleEndedIterator for UpdateWithSeq<I, S, F>
+where
+    I: DoubleEndedIterator,
+    F: FnMut(&mut S, &mut I::Item),
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if let Some(mut v) = self.base.next_back() {
+            (self.update_op)(&mut self.state, &mut v);
+            Some(v)
+        } else {
+            None
+        }
+    }
+}
+
+/// ////////////////////////////////////////////////////////////////////////
+
+/// Mutates the elements of `base` in place, short-circuiting as soon as
+/// any `update_op` invocation returns `Err`.
+///
+/// This is the implementation backing the [`try_update()`] method on
+/// [`ParallelIterator`]; like [`try_for_each()`], it is a terminal
+/// operation rather than a further adaptor.
+///
+/// NB: a free fn because it is NOT part of the end-user API.
+///
+/// [`try_update()`]: trait.ParallelIterator.html#method.try_update
+/// [`try_for_each()`]: trait.ParallelIterator.html#method.try_for_each
+/// [`ParallelIterator`]: trait.ParallelIterator.html
+pub fn try_update<I, F, E>(base: I, update_op: F) -> Result<(), E>
+where
+    I: ParallelIterator,
+    F: Fn(&mut I::Item) -> Result<(), E> + Sync,
+    E: Send,
+{
+    let consumer = TryUpdateConsumer {
+        update_op: &update_op,
+        failed: Arc::new(AtomicBool::new(false)),
+    };
+    base.drive_unindexed(consumer)
+}
+
+struct TryUpdateConsumer<'f, F: 'f> {
+    update_op: &'f F,
+    failed: Arc<AtomicBool>,
+}
+
+impl<'f, F> Clone for TryUpdateConsumer<'f, F> {
+    fn clone(&self) -> Self {
+        TryUpdateConsumer {
+            update_op: self.update_op,
+            failed: self.failed.clone(),
+        }
+    }
+}
+
+impl<'f, T, F, E> Consumer<T> for TryUpdateConsumer<'f, F>
+where
+    F: Fn(&mut T) -> Result<(), E> + Sync,
+    E: Send,
+{
+    type Folder = TryUpdateFolder<'f, F, E>;
+    type Reducer = TryUpdateReducer<E>;
+    type Result = Result<(), E>;
+
+    fn split_at(self, _index: usize) -> (Self, Self, Self::Reducer) {
+        (
+            self.clone(),
+            self,
+            TryUpdateReducer {
+                marker: PhantomData,
+            },
+        )
+    }
+
+    fn into_folder(self) -> Self::Folder {
+        TryUpdateFolder {
+            update_op: self.update_op,
+            failed: self.failed,
+            error: None,
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+impl<'f, T, F, E> UnindexedConsumer<T> for TryUpdateConsumer<'f, F>
+where
+    F: Fn(&mut T) -> Result<(), E> + Sync,
+    E: Send,
+{
+    fn split_off_left(&self) -> Self {
+        self.clone()
+    }
+
+    fn to_reducer(&self) -> Self::Reducer {
+        TryUpdateReducer {
+            marker: PhantomData,
+        }
+    }
+}
+
+struct TryUpdateFolder<'f, F: 'f, E> {
+    update_op: &'f F,
+    failed: Arc<AtomicBool>,
+    error: Option<E>,
+}
+
+impl<'f, T, F, E> Folder<T> for TryUpdateFolder<'f, F, E>
+where
+    F: Fn(&mut T) -> Result<(), E> + Sync,
+{
+    type Result = Result<(), E>;
+
+    fn consume(mut self, mut item: T) -> Self {
+        if !self.failed.load(Ordering::Relaxed) {
+            if let Err(e) = (self.update_op)(&mut item) {
+                self.failed.store(true, Ordering::Relaxed);
+                self.error = Some(e);
+            }
+        }
+        self
+    }
+
+    fn complete(self) -> Self::Result {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+struct TryUpdateReducer<E> {
+    marker: PhantomData<E>,
+}
+
+impl<E> Reducer<Result<(), E>> for TryUpdateReducer<E> {
+    fn reduce(self, left: Result<(), E>, right: Result<(), E>) -> Result<(), E> {
+        // Prefer the first error, in iteration order.
+        left.and(right)
+    }
+}
+
+/// ////////////////////////////////////////////////////////////////////////
+
+/// `WithSetup` attaches a [`Setup`] to a parallel iterator chain, letting
+/// callers tune splitting granularity once for the whole pipeline instead
+/// of sprinkling `with_min_len`/`with_max_len` on individual stages. It
+/// applies `setup.min_len`/`setup.max_len` to the producer it wraps (see
+/// [`SetupProducer`]), so they are actually consulted when rayon decides
+/// whether to split further, not merely stored. This holds for indexed
+/// driving (`drive`/`with_producer`); `drive_unindexed` drives the base
+/// directly and does not apply the setup.
+///
+/// This struct is created by a `with_setup()` method, analogous to
+/// `with_min_len()`/`with_max_len()`.
+///
+/// [`Setup`]: struct.Setup.html
+/// [`SetupProducer`]: struct.SetupProducer.html
+#[must_use = "iterator adaptors are lazy and do nothing unless consumed"]
+#[derive(Clone, Debug)]
+pub struct WithSetup<I> {
+    base: I,
+    setup: Setup,
+}
+
+/// Create a new `WithSetup` iterator.
+///
+/// NB: a free fn because it is NOT part of the end-user API.
+pub fn new_with_setup<I>(base: I, setup: Setup) -> WithSetup<I>
+where
+    I: ParallelIterator,
+{
+    WithSetup {
+        base: base,
+        setup: setup,
+    }
+}
+
+impl<I> WithSetup<I> {
+    /// Returns the `Setup` this adaptor was constructed with.
+    pub fn setup(&self) -> Setup {
+        self.setup
+    }
+}
+
+impl<I> ParallelIterator for WithSetup<I>
+where
+    I: ParallelIterator,
+{
+    type Item = I::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.base.drive_unindexed(consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.base.opt_len()
+    }
+}
+
+impl<I> IndexedParallelIterator for WithSetup<I>
+where
+    I: IndexedParallelIterator,
+{
+    fn drive<C>(self, consumer: C) -> C::Result
+    where
+        C: Consumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    fn len(&self) -> usize {
+        self.base.len()
+    }
+
+    fn with_producer<CB>(self, callback: CB) -> CB::Output
+    where
+        CB: ProducerCallback<Self::Item>,
+    {
+        let setup = self.setup;
+        return self.base.with_producer(Callback {
+            callback: callback,
+            setup: setup,
+        });
+
+        struct Callback<CB> {
+            callback: CB,
+            setup: Setup,
+        }
+
+        impl<T, CB> ProducerCallback<T> for Callback<CB>
+        where
+            CB: ProducerCallback<T>,
+        {
+            type Output = CB::Output;
+
+            fn callback<P>(self, base: P) -> CB::Output
+            where
+                P: Producer<Item = T>,
+            {
+                let producer = SetupProducer {
+                    base: base,
+                    setup: self.setup,
+                };
+                self.callback.callback(producer)
+            }
+        }
+    }
+}
+
+/// Producer that replaces `min_len`/`max_len` with the values configured on a
+/// [`WithSetup`], falling back to the wrapped producer's own values when a
+/// field is unset.
+///
+/// [`WithSetup`]: struct.WithSetup.html
+struct SetupProducer<P> {
+    base: P,
+    setup: Setup,
+}
+
+impl<P> Producer for SetupProducer<P>
+where
+    P: Producer,
+{
+    type Item = P::Item;
+    type IntoIter = P::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.base.into_iter()
+    }
+
+    fn min_len(&self) -> usize {
+        self.setup.min_len.unwrap_or_else(|| self.base.min_len())
+    }
+
+    fn max_len(&self) -> usize {
+        self.setup.max_len.unwrap_or_else(|| self.base.max_len())
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let (left, right) = self.base.split_at(index);
+        (
+            SetupProducer {
+                base: left,
+                setup: self.setup,
+            },
+            SetupProducer {
+                base: right,
+                setup: self.setup,
+            },
+        )
+    }
+
+    fn fold_with<G>(self, folder: G) -> G
+    where
+        G: Folder<Self::Item>,
+    {
+        self.base.fold_with(folder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Minimal `Producer` over a `Vec`, just enough to exercise
+    /// `SetupProducer` without depending on rayon's real collection
+    /// producers (not part of this module).
+    struct VecProducer<T> {
+        data: Vec<T>,
+    }
+
+    impl<T: Send> Producer for VecProducer<T> {
+        type Item = T;
+        type IntoIter = ::std::vec::IntoIter<T>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.data.into_iter()
+        }
+
+        fn min_len(&self) -> usize {
+            1
+        }
+
+        fn max_len(&self) -> usize {
+            self.data.len()
+        }
+
+        fn split_at(self, index: usize) -> (Self, Self) {
+            let mut data = self.data;
+            let right = data.split_off(index);
+            (VecProducer { data: data }, VecProducer { data: right })
+        }
+
+        fn fold_with<G>(self, folder: G) -> G
+        where
+            G: Folder<Self::Item>,
+        {
+            folder.consume_iter(self.into_iter())
+        }
+    }
+
+    #[test]
+    fn update_with_index_threads_offset_across_splits() {
+        let whole: Vec<i32> = UpdateIndexedSeq {
+            base: vec![10, 20, 30, 40, 50].into_iter(),
+            update_op: |i: usize, v: &mut i32| *v += i as i32,
+            index: 0,
+        }
+        .collect();
+
+        // Split the same data at index 3, the way `UpdateIndexedProducer::split_at`
+        // would: the left half keeps offset 0, the right half gets offset 3.
+        let mut left: Vec<i32> = UpdateIndexedSeq {
+            base: vec![10, 20, 30].into_iter(),
+            update_op: |i: usize, v: &mut i32| *v += i as i32,
+            index: 0,
+        }
+        .collect();
+        let right: Vec<i32> = UpdateIndexedSeq {
+            base: vec![40, 50].into_iter(),
+            update_op: |i: usize, v: &mut i32| *v += i as i32,
+            index: 3,
+        }
+        .collect();
+        left.extend(right);
+
+        assert_eq!(whole, left);
+        assert_eq!(whole, vec![10, 21, 32, 43, 54]);
+    }
+
+    #[test]
+    fn update_with_clones_state_per_job() {
+        // Mimics `UpdateWithProducer::split_at`: each branch gets its own
+        // clone of `init`, so mutating one branch's state must not affect
+        // the other's.
+        let init: Vec<i32> = Vec::new();
+
+        let left: Vec<i32> = UpdateWithSeq {
+            base: vec![1, 2, 3].into_iter(),
+            state: init.clone(),
+            update_op: |state: &mut Vec<i32>, v: &mut i32| {
+                state.push(*v);
+                *v = state.len() as i32;
+            },
+        }
+        .collect();
+
+        let right: Vec<i32> = UpdateWithSeq {
+            base: vec![7, 8].into_iter(),
+            state: init,
+            update_op: |state: &mut Vec<i32>, v: &mut i32| {
+                state.push(*v);
+                *v = state.len() as i32;
+            },
+        }
+        .collect();
+
+        // If state leaked across jobs, `right`'s running length would
+        // continue from 3 instead of restarting at 1.
+        assert_eq!(left, vec![1, 2, 3]);
+        assert_eq!(right, vec![1, 2]);
+    }
+
+    #[test]
+    fn try_update_folder_stops_on_error() {
+        let failed = Arc::new(AtomicBool::new(false));
+        let op = |v: &mut i32| -> Result<(), &'static str> {
+            if *v < 0 {
+                Err("negative")
+            } else {
+                *v *= 2;
+                Ok(())
+            }
+        };
+
+        let mut folder = TryUpdateFolder {
+            update_op: &op,
+            failed: failed.clone(),
+            error: None,
+        };
+        assert!(!folder.full());
+        folder = folder.consume(1);
+        folder = folder.consume(-1);
+        assert!(folder.full());
+        assert!(failed.load(Ordering::Relaxed));
+        assert_eq!(folder.complete(), Err("negative"));
+    }
+
+    #[test]
+    fn try_update_shares_failure_across_splits() {
+        let failed = Arc::new(AtomicBool::new(false));
+        let op = |v: &mut i32| -> Result<(), &'static str> {
+            if *v < 0 {
+                Err("negative")
+            } else {
+                Ok(())
+            }
+        };
+
+        let mut left = TryUpdateFolder {
+            update_op: &op,
+            failed: failed.clone(),
+            error: None,
+        };
+        let right = TryUpdateFolder {
+            update_op: &op,
+            failed: failed.clone(),
+            error: None,
+        };
+
+        left = left.consume(-1);
+        assert!(left.full());
+        // `right` never saw a failing item itself, but the shared flag
+        // must still report it as full so the remaining work is skipped.
+        assert!(right.full());
+
+        let reducer = TryUpdateReducer {
+            marker: PhantomData,
+        };
+        assert_eq!(
+            reducer.reduce(left.complete(), right.complete()),
+            Err("negative")
+        );
+
+        // A folder that only ever sees successful items, but shares a
+        // flag that was never tripped, still completes with `Ok`.
+        let fresh_failed = Arc::new(AtomicBool::new(false));
+        let untouched = TryUpdateFolder {
+            update_op: &op,
+            failed: fresh_failed,
+            error: None,
+        }
+        .consume(2);
+        assert_eq!(untouched.complete(), Ok(()));
+    }
+
+    #[test]
+    fn update_indexed_producer_split_at_propagates_offset() {
+        // Drives the real `UpdateIndexedProducer::split_at`, which is the
+        // production code responsible for the `offset`/`offset + index`
+        // arithmetic — not a hand-rolled stand-in for it.
+        let op = |i: usize, v: &mut i32| *v += i as i32;
+        let producer = UpdateIndexedProducer {
+            base: VecProducer {
+                data: vec![10, 20, 30, 40, 50],
+            },
+            update_op: &op,
+            offset: 0,
+        };
+
+        let (left, right) = producer.split_at(3);
+        let left: Vec<i32> = left.into_iter().collect();
+        let right: Vec<i32> = right.into_iter().collect();
+
+        assert_eq!(left, vec![10, 21, 32]);
+        assert_eq!(right, vec![43, 54]);
+    }
+
+    #[test]
+    fn update_with_producer_split_at_clones_state_independently() {
+        // Drives the real `UpdateWithProducer::split_at`, which is what
+        // actually clones `init` for the left half.
+        let op = |state: &mut Vec<i32>, v: &mut i32| {
+            state.push(*v);
+            *v = state.len() as i32;
+        };
+        let producer = UpdateWithProducer {
+            base: VecProducer {
+                data: vec![1, 2, 3, 7, 8],
+            },
+            init: Vec::<i32>::new(),
+            update_op: &op,
+        };
+
+        let (left, right) = producer.split_at(3);
+        let left: Vec<i32> = left.into_iter().collect();
+        let right: Vec<i32> = right.into_iter().collect();
+
+        // If `split_at` shared `init` instead of cloning it, `right`'s
+        // running length would continue from 3 instead of restarting at 1.
+        assert_eq!(left, vec![1, 2, 3]);
+        assert_eq!(right, vec![1, 2]);
+    }
+
+    #[test]
+    fn try_update_consumer_split_at_shares_failure_flag() {
+        // Drives the real `Consumer::split_at` for `TryUpdateConsumer`,
+        // which is what actually clones the shared `Arc<AtomicBool>`.
+        let op = |v: &mut i32| -> Result<(), &'static str> {
+            if *v < 0 {
+                Err("negative")
+            } else {
+                Ok(())
+            }
+        };
+        let consumer = TryUpdateConsumer {
+            update_op: &op,
+            failed: Arc::new(AtomicBool::new(false)),
+        };
+
+        let (left_consumer, right_consumer, _reducer) = consumer.split_at(0);
+
+        let mut left_folder = left_consumer.into_folder();
+        left_folder = left_folder.consume(-1);
+        assert!(left_folder.full());
+
+        // `right_consumer` shares the flag only if `split_at`/`Clone`
+        // actually shares the `Arc` rather than minting a fresh one.
+        assert!(right_consumer.full());
+        assert_eq!(left_folder.complete(), Err("negative"));
+    }
+
+    #[test]
+    fn try_update_consumer_split_off_left_shares_failure_flag() {
+        // Drives the real `UnindexedConsumer::split_off_left`.
+        let op = |v: &mut i32| -> Result<(), &'static str> {
+            if *v < 0 {
+                Err("negative")
+            } else {
+                Ok(())
+            }
+        };
+        let consumer = TryUpdateConsumer {
+            update_op: &op,
+            failed: Arc::new(AtomicBool::new(false)),
+        };
+
+        let left_consumer = consumer.split_off_left();
+        let mut left_folder = left_consumer.into_folder();
+        left_folder = left_folder.consume(-1);
+        assert!(left_folder.full());
+
+        let right_folder = consumer.into_folder();
+        assert!(right_folder.full());
+    }
+
+    #[test]
+    fn setup_producer_prefers_explicit_values_over_base() {
+        let base = VecProducer {
+            data: vec![1, 2, 3, 4],
+        };
+        let producer = SetupProducer {
+            base: base,
+            setup: Setup {
+                min_len: Some(2),
+                max_len: None,
+                splits: None,
+            },
+        };
+
+        assert_eq!(producer.min_len(), 2); // explicit Setup value wins
+        assert_eq!(producer.max_len(), 4); // unset, falls back to the base producer
+
+        let (left, right) = producer.split_at(2);
+        assert_eq!(left.base.data, vec![1, 2]);
+        assert_eq!(right.base.data, vec![3, 4]);
+        assert_eq!(left.min_len(), 2);
+        assert_eq!(right.min_len(), 2);
+    }
+}